@@ -0,0 +1,141 @@
+use na::{DMatrix, DVector};
+use nalgebra as na;
+
+/// Per-block SVD of a poke matrix, cached once and re-thresholded cheaply.
+///
+/// `CalibrationVault::new` factors each poke-matrix block (one per guide-star
+/// WFS in `main`) as `U * diag(s) * Vᵀ`
+/// a single time. `n_threshold`/`relative_threshold` then rebuild the reconstructor
+/// `V * diag(1/s_kept) * Uᵀ` straight from the cached factors, without touching the
+/// SVD again, so sweeping the truncation is effectively free.
+#[derive(Clone)]
+pub struct CalibrationVault {
+    svd: Vec<(DMatrix<f64>, DVector<f64>, DMatrix<f64>)>,
+    mat: Vec<DMatrix<f64>>,
+    zeros: Vec<usize>,
+}
+impl CalibrationVault {
+    /// Computes and caches the SVD of each poke-matrix block, then builds
+    /// the reconstructor using every singular value (no truncation).
+    pub fn new(poke_mat: Vec<DMatrix<f64>>) -> Self {
+        let svd: Vec<_> = poke_mat
+            .into_iter()
+            .map(|mat| {
+                let svd = mat.svd(true, true);
+                (
+                    svd.u.expect("SVD left singular vectors missing"),
+                    svd.singular_values,
+                    svd.v_t.expect("SVD right singular vectors missing"),
+                )
+            })
+            .collect();
+        let mut vault = Self {
+            svd,
+            mat: vec![],
+            zeros: vec![],
+        };
+        vault.rebuild(None);
+        vault
+    }
+    /// Drops the `n` smallest singular values of every block and rebuilds
+    /// the reconstructor from the cached SVD.
+    pub fn n_threshold(&mut self, n: usize) -> &mut Self {
+        self.rebuild(Some(Threshold::N(n)));
+        self
+    }
+    /// Drops any singular value `s_i < t * s_0` (`s_0` the largest) of every
+    /// block and rebuilds the reconstructor from the cached SVD.
+    pub fn relative_threshold(&mut self, t: f64) -> &mut Self {
+        self.rebuild(Some(Threshold::Relative(t)));
+        self
+    }
+    /// Sets the mode indices, within each segment's full-length mode vector,
+    /// that are excluded from the estimate and must be re-inserted as zeros
+    /// by the caller (e.g. `Reconstructor::write`).
+    pub fn insert_zeros(&mut self, indices: Vec<usize>) -> &mut Self {
+        self.zeros = indices;
+        self
+    }
+    /// Mode indices re-inserted as zeros in the full-length mode vector.
+    pub fn zeros(&self) -> &[usize] {
+        &self.zeros
+    }
+    /// The reconstructor `V * diag(1/s_kept) * Uᵀ`, one matrix per block.
+    pub fn reconstructor(&self) -> Vec<DMatrix<f64>> {
+        self.mat.clone()
+    }
+    /// Rebuilds a vault from a previously cached per-block SVD, e.g. loaded
+    /// from disk, skipping the `svd()` computation.
+    pub fn from_svd(svd: Vec<(DMatrix<f64>, DVector<f64>, DMatrix<f64>)>) -> Self {
+        let mut vault = Self {
+            svd,
+            mat: vec![],
+            zeros: vec![],
+        };
+        vault.rebuild(None);
+        vault
+    }
+    /// The cached per-block `(U, s, Vᵀ)` triplets, e.g. to persist to disk.
+    pub fn svd(&self) -> &[(DMatrix<f64>, DVector<f64>, DMatrix<f64>)] {
+        &self.svd
+    }
+    fn rebuild(&mut self, threshold: Option<Threshold>) {
+        self.mat = self
+            .svd
+            .iter()
+            .map(|(u, s, v_t)| {
+                let n_kept = match threshold {
+                    None => s.len(),
+                    Some(Threshold::N(n)) => s.len().saturating_sub(n),
+                    Some(Threshold::Relative(t)) => {
+                        s.iter().take_while(|&&s_i| s_i >= t * s[0]).count()
+                    }
+                };
+                let u = u.columns(0, n_kept);
+                let v = v_t.rows(0, n_kept).transpose();
+                let s_inv = DMatrix::from_diagonal(&s.rows(0, n_kept).map(|s_i| 1. / s_i));
+                v * s_inv * u.transpose()
+            })
+            .collect();
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Threshold {
+    N(usize),
+    Relative(f64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_zeros_roundtrips_through_zeros() {
+        let mut vault = CalibrationVault::new(vec![DMatrix::identity(2, 2)]);
+        assert!(vault.zeros().is_empty());
+        vault.insert_zeros(vec![1, 3]);
+        assert_eq!(vault.zeros(), &[1, 3]);
+    }
+
+    #[test]
+    fn full_reconstructor_is_the_pseudo_inverse() {
+        let poke = DMatrix::from_row_slice(2, 2, &[2., 0., 0., 1.]);
+        let vault = CalibrationVault::new(vec![poke]);
+        let mat = vault.reconstructor();
+        let expected = DMatrix::from_row_slice(2, 2, &[0.5, 0., 0., 1.]);
+        assert!((&mat[0] - &expected).norm() < 1e-10);
+    }
+
+    #[test]
+    fn n_threshold_drops_the_smallest_singular_value() {
+        let poke = DMatrix::from_row_slice(2, 2, &[2., 0., 0., 1.]);
+        let mut vault = CalibrationVault::new(vec![poke]);
+        vault.n_threshold(1);
+        let mat = vault.reconstructor();
+        // Only the largest singular value (2.) survives, so the second
+        // row/column of the full inverse is truncated away.
+        let expected = DMatrix::from_row_slice(2, 2, &[0.5, 0., 0., 0.]);
+        assert!((&mat[0] - &expected).norm() < 1e-10);
+    }
+}
@@ -0,0 +1,211 @@
+use na::DMatrix;
+use nalgebra as na;
+
+use crate::tomographic_reconstructor::{residual_covariance, Cn2Layer, GuideStar, APERTURE_DIAMETER};
+
+/// Decomposed wavefront-error budget: one term per known contributor, plus
+/// their root-sum-square, the quantity usually quoted as "the" residual WFE.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WfeBudget {
+    pub tomographic: f64,
+    pub fitting: f64,
+    pub aliasing: f64,
+    pub noise: f64,
+}
+impl WfeBudget {
+    pub fn rss(&self) -> f64 {
+        (self.tomographic.powi(2) + self.fitting.powi(2) + self.aliasing.powi(2) + self.noise.powi(2)).sqrt()
+    }
+}
+
+/// Reactive, `LinearActiveOptics`-style error-budget model: holds the same
+/// per-layer guide-star interaction matrices and exit-pupil modal projection
+/// already computed for the [`TomographicReconstructor`], and re-derives the
+/// [`WfeBudget`] from them whenever a tracked property is swept, without
+/// launching a GPU simulation.
+///
+/// `fitting` and `aliasing` are fixed instrument terms (DM actuator count,
+/// WFS sub-aperture sampling) supplied once at construction: sweeping the
+/// tracked properties below doesn't move them, since none of
+/// `guide_star_z_arcmin`/`n_sensor`/`gain`/`noise_variance`/the Cn² profile
+/// changes the DM or the WFS geometry. `tomographic` (from the tomographic
+/// fusion) and `noise` (from the WFS noise variance and integrator gain) are
+/// re-derived on every change, since those are exactly the terms those
+/// properties drive.
+///
+/// [`TomographicReconstructor`]: crate::tomographic_reconstructor::TomographicReconstructor
+pub struct ErrorBudget {
+    layer_interaction: Vec<DMatrix<f64>>,
+    projection: DMatrix<f64>,
+    r0: f64,
+    outer_scale: f64,
+    fitting: f64,
+    aliasing: f64,
+    guide_star_z_arcmin: f32,
+    n_sensor: usize,
+    gain: f64,
+    noise_variance: f64,
+    turbulence: Vec<Cn2Layer>,
+    budget: WfeBudget,
+}
+impl ErrorBudget {
+    /// `layer_interaction`: per-layer, on-axis WFS interaction matrix, as fed
+    /// to [`TomographicReconstructor::new`]. `projection`: exit-pupil modal
+    /// projection of the stacked layer phases, as fed to the same
+    /// constructor. `n_mode`/`n_side_lenslet`: corrected mode count and
+    /// lenslet count, to size the fixed fitting/aliasing terms. `r0`/
+    /// `outer_scale`: total Fried parameter [m] and von Kármán outer scale
+    /// [m] of the atmosphere, same convention as
+    /// [`TomographicReconstructor::new`]; held fixed, since neither the DM
+    /// fitting error nor the WFS aliasing error depends on guide-star
+    /// geometry or gain.
+    pub fn new(
+        layer_interaction: Vec<DMatrix<f64>>,
+        projection: DMatrix<f64>,
+        n_mode: usize,
+        n_side_lenslet: usize,
+        guide_star_z_arcmin: f32,
+        n_sensor: usize,
+        gain: f64,
+        noise_variance: f64,
+        r0: f64,
+        outer_scale: f64,
+        turbulence: Vec<Cn2Layer>,
+    ) -> Self {
+        let mut budget = Self {
+            layer_interaction,
+            projection,
+            r0,
+            outer_scale,
+            fitting: fitting_error(n_mode, r0),
+            aliasing: aliasing_error(n_side_lenslet, r0),
+            guide_star_z_arcmin,
+            n_sensor,
+            gain,
+            noise_variance,
+            turbulence,
+            budget: WfeBudget::default(),
+        };
+        budget.recompute();
+        budget
+    }
+    pub fn set_guide_star_z_arcmin(&mut self, guide_star_z_arcmin: f32) -> &mut Self {
+        self.guide_star_z_arcmin = guide_star_z_arcmin;
+        self.recompute();
+        self
+    }
+    pub fn set_n_sensor(&mut self, n_sensor: usize) -> &mut Self {
+        self.n_sensor = n_sensor;
+        self.recompute();
+        self
+    }
+    pub fn set_gain(&mut self, gain: f64) -> &mut Self {
+        self.gain = gain;
+        self.recompute();
+        self
+    }
+    pub fn set_noise_variance(&mut self, noise_variance: f64) -> &mut Self {
+        self.noise_variance = noise_variance;
+        self.recompute();
+        self
+    }
+    pub fn set_turbulence(&mut self, turbulence: Vec<Cn2Layer>) -> &mut Self {
+        self.turbulence = turbulence;
+        self.recompute();
+        self
+    }
+    /// The budget derived from the properties as last set.
+    pub fn budget(&self) -> WfeBudget {
+        self.budget
+    }
+    fn guide_stars(&self) -> Vec<GuideStar> {
+        (0..self.n_sensor)
+            .map(|k| GuideStar {
+                zenith: (self.guide_star_z_arcmin as f64 / 60.).to_radians(),
+                azimuth: 2. * std::f64::consts::PI * k as f64 / self.n_sensor as f64,
+            })
+            .collect()
+    }
+    fn recompute(&mut self) {
+        let guide_stars = self.guide_stars();
+        let c_res = residual_covariance(
+            &self.turbulence,
+            &self.layer_interaction,
+            &guide_stars,
+            self.r0,
+            self.outer_scale,
+            self.noise_variance,
+        );
+        let n = self.projection.nrows() as f64;
+        let tomographic_var = (&self.projection * &c_res * self.projection.transpose()).trace() / n;
+        // Closed-loop noise propagation: with integrator gain `g`, the
+        // noise-only contribution to the corrected variance scales as
+        // `g / (2 - g)` relative to the open-loop WFS noise variance.
+        let noise_var = self.noise_variance * self.gain / (2. - self.gain).max(f64::EPSILON);
+        self.budget = WfeBudget {
+            tomographic: tomographic_var.max(0.).sqrt(),
+            fitting: self.fitting,
+            aliasing: self.aliasing,
+            noise: noise_var.max(0.).sqrt(),
+        };
+    }
+}
+
+// Hudgin (1977)-style fitting-error coefficient for a continuous-face-sheet
+// DM: variance = FITTING_COEFFICIENT * (actuator_pitch / r0)^(5/3) [rad²].
+const FITTING_COEFFICIENT: f64 = 0.28;
+// Rigaut et al. (1998)-style SH-WFS aliasing coefficient:
+// variance = ALIASING_COEFFICIENT * (lenslet_pitch / r0)^(5/3) [rad²].
+const ALIASING_COEFFICIENT: f64 = 0.07;
+
+/// DM fitting error [rad RMS], from the actuator pitch implied by `n_mode`
+/// correctable degrees of freedom spread over [`APERTURE_DIAMETER`] and the
+/// Fried parameter `r0` [m]: `sigma = sqrt(0.28 * (d/r0)^(5/3))`.
+fn fitting_error(n_mode: usize, r0: f64) -> f64 {
+    let pitch = APERTURE_DIAMETER / (n_mode as f64).sqrt();
+    (FITTING_COEFFICIENT * (pitch / r0).powf(5. / 3.)).sqrt()
+}
+
+/// SH-WFS slope-aliasing error [rad RMS], from the lenslet pitch implied by
+/// `n_side_lenslet` sub-apertures across [`APERTURE_DIAMETER`] and the Fried
+/// parameter `r0` [m]: `sigma = sqrt(0.07 * (d/r0)^(5/3))`.
+fn aliasing_error(n_side_lenslet: usize, r0: f64) -> f64 {
+    let pitch = APERTURE_DIAMETER / n_side_lenslet as f64;
+    (ALIASING_COEFFICIENT * (pitch / r0).powf(5. / 3.)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fitting_error_matches_closed_form() {
+        let n_mode = 100;
+        let r0 = 0.15;
+        let pitch = APERTURE_DIAMETER / (n_mode as f64).sqrt();
+        let expected = (0.28 * (pitch / r0).powf(5. / 3.)).sqrt();
+        assert!((fitting_error(n_mode, r0) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn aliasing_error_matches_closed_form() {
+        let n_side_lenslet = 60;
+        let r0 = 0.15;
+        let pitch = APERTURE_DIAMETER / n_side_lenslet as f64;
+        let expected = (0.07 * (pitch / r0).powf(5. / 3.)).sqrt();
+        assert!((aliasing_error(n_side_lenslet, r0) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn finer_correction_reduces_both_error_terms() {
+        let r0 = 0.15;
+        assert!(fitting_error(400, r0) < fitting_error(100, r0));
+        assert!(aliasing_error(120, r0) < aliasing_error(60, r0));
+    }
+
+    #[test]
+    fn worse_seeing_increases_both_error_terms() {
+        assert!(fitting_error(100, 0.10) > fitting_error(100, 0.20));
+        assert!(aliasing_error(60, 0.10) > aliasing_error(60, 0.20));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use na::{DMatrix, DVector};
+use nalgebra as na;
+
+use crseo_client::{M2modes, SegmentPiston, SegmentWfeRms, WfeRms};
+use dos_actors::{
+    io::{Data, Read, Write},
+    prelude::Tick,
+    Update,
+};
+
+/// Linear, ray-trace-free stand-in for a ray-traced `OpticalModel`.
+///
+/// Holds sensitivity matrices, built once from the calibration's KL basis
+/// and pupil mask and serialized to disk, that map `M2modes` directly to
+/// segment piston, per-segment wavefront RMS and masked wavefront RMS. No
+/// tip-tilt or segment tip-tilt output is provided. It
+/// implements the same `Read<M2modes>`/`Write<SegmentPiston>`/`Write<WfeRms>`
+/// contract as `OpticalModel`'s aliases, so it can be dropped into the actor
+/// graph wherever only the modal residual metrics are needed, skipping the
+/// GPU ray trace entirely; the diffractive `OpticalModel` is still required
+/// for the final PSF/PSSn snapshots.
+///
+/// Not yet exercised this way: `main`'s `FAST_RESIDUALS` demo only runs this
+/// type standalone, so the "drop-in for `science`" claim above is
+/// structural (the trait impls match), not integration-tested against a
+/// live actor graph.
+pub struct LinearOpticalModel {
+    m2modes_to_segment_piston: DMatrix<f64>,
+    m2modes_to_segment_wfe_rms: DMatrix<f64>,
+    m2modes_wfe_gram: DMatrix<f64>,
+    m2modes: DVector<f64>,
+}
+impl LinearOpticalModel {
+    /// Builds the sensitivity matrices directly from the M2 Karhunen-Loève
+    /// modal convention used throughout this crate: `n_mode_per_segment`
+    /// coefficients per segment, mode index 0 of each segment's block is
+    /// segment piston (the same convention [`CalibrationVault::insert_zeros`]
+    /// relies on). GMT M2 KL modes are orthonormal and unit-RMS-normalized
+    /// over each segment's pupil by construction, so no ray trace is needed
+    /// to recover the pupil-masked metrics calibration already defines:
+    /// segment piston is a direct selector, and wavefront RMS over a mode set
+    /// is the Euclidean norm of its (non-piston) coefficients, i.e. a Gram
+    /// matrix that is the identity restricted to those modes.
+    pub fn kl_sensitivities(
+        n_segment: usize,
+        n_mode_per_segment: usize,
+    ) -> (DMatrix<f64>, DMatrix<f64>, DMatrix<f64>) {
+        let n_mode = n_segment * n_mode_per_segment;
+        let mut m2modes_to_segment_piston = DMatrix::zeros(n_segment, n_mode);
+        let mut m2modes_to_segment_wfe_rms = DMatrix::zeros(n_segment, n_mode);
+        let mut m2modes_wfe_gram = DMatrix::zeros(n_mode, n_mode);
+        for segment in 0..n_segment {
+            let base = segment * n_mode_per_segment;
+            m2modes_to_segment_piston[(segment, base)] = 1.;
+            for mode in 1..n_mode_per_segment {
+                m2modes_to_segment_wfe_rms[(segment, base + mode)] = 1.;
+                m2modes_wfe_gram[(base + mode, base + mode)] = 1.;
+            }
+        }
+        (
+            m2modes_to_segment_piston,
+            m2modes_to_segment_wfe_rms,
+            m2modes_wfe_gram,
+        )
+    }
+    /// `m2modes_to_segment_piston`: one row per segment, a direct selector of
+    /// each segment's piston mode. `m2modes_to_segment_wfe_rms`: one row per
+    /// segment, a 0/1 mask of that segment's non-piston modes, squared and
+    /// summed by [`Self::segment_wfe_rms`] since RMS is a norm, not linear in
+    /// the coefficients. `m2modes_wfe_gram`: the masked-pupil modal Gram
+    /// matrix `G` (`n_mode x n_mode`, symmetric positive-semidefinite) such
+    /// that wavefront RMS over the mask is `sqrt(aᵀ·G·a)` for modal
+    /// coefficients `a`.
+    pub fn new(
+        m2modes_to_segment_piston: DMatrix<f64>,
+        m2modes_to_segment_wfe_rms: DMatrix<f64>,
+        m2modes_wfe_gram: DMatrix<f64>,
+    ) -> Self {
+        let n_mode = m2modes_wfe_gram.nrows();
+        assert_eq!(n_mode, m2modes_wfe_gram.ncols());
+        assert_eq!(n_mode, m2modes_to_segment_piston.ncols());
+        assert_eq!(n_mode, m2modes_to_segment_wfe_rms.ncols());
+        Self {
+            m2modes_to_segment_piston,
+            m2modes_to_segment_wfe_rms,
+            m2modes_wfe_gram,
+            m2modes: DVector::zeros(n_mode),
+        }
+    }
+    /// Loads the sensitivity matrices cached by a previous call, or builds
+    /// and caches them with `build` if `path` doesn't exist yet.
+    pub fn cached(
+        path: &Path,
+        build: impl FnOnce() -> (DMatrix<f64>, DMatrix<f64>, DMatrix<f64>),
+    ) -> anyhow::Result<Self> {
+        let (m2modes_to_segment_piston, m2modes_to_segment_wfe_rms, m2modes_wfe_gram) =
+            if path.is_file() {
+                println!("Loading linear optical model sensitivities from {:?}", path);
+                let data: (
+                    ((usize, usize), Vec<f64>),
+                    ((usize, usize), Vec<f64>),
+                    ((usize, usize), Vec<f64>),
+                ) = bincode::deserialize_from(File::open(path)?)?;
+                let ((p_shape, p), (w_shape, w), (g_shape, g)) = data;
+                (
+                    DMatrix::from_column_slice(p_shape.0, p_shape.1, &p),
+                    DMatrix::from_column_slice(w_shape.0, w_shape.1, &w),
+                    DMatrix::from_column_slice(g_shape.0, g_shape.1, &g),
+                )
+            } else {
+                println!("Computing linear optical model sensitivities");
+                let (p, w, g) = build();
+                println!("Saving linear optical model sensitivities to {:?}", path);
+                bincode::serialize_into(
+                    File::create(path)?,
+                    &(
+                        (p.shape(), p.as_slice().to_vec()),
+                        (w.shape(), w.as_slice().to_vec()),
+                        (g.shape(), g.as_slice().to_vec()),
+                    ),
+                )?;
+                (p, w, g)
+            };
+        Ok(Self::new(
+            m2modes_to_segment_piston,
+            m2modes_to_segment_wfe_rms,
+            m2modes_wfe_gram,
+        ))
+    }
+    /// Masked-pupil wavefront RMS `sqrt(aᵀ·G·a)` at the current `m2modes`,
+    /// clamped to zero against the roundoff that can otherwise drive the
+    /// quadratic form slightly negative.
+    fn wfe_rms(&self) -> f64 {
+        (self.m2modes.dot(&(&self.m2modes_wfe_gram * &self.m2modes)))
+            .max(0.)
+            .sqrt()
+    }
+    /// Per-segment wavefront RMS: `m2modes_to_segment_wfe_rms` masks each
+    /// segment's non-piston modes, so the squared coefficients are summed
+    /// (not the coefficients themselves) before the per-segment square root,
+    /// for the same reason [`Self::wfe_rms`] goes through a quadratic form
+    /// rather than a single linear row.
+    fn segment_wfe_rms(&self) -> Vec<f64> {
+        let squared = self.m2modes.map(|a| a * a);
+        (&self.m2modes_to_segment_wfe_rms * &squared)
+            .map(|v| v.max(0.).sqrt())
+            .as_slice()
+            .to_vec()
+    }
+}
+impl Update for LinearOpticalModel {
+    fn update(&mut self) {}
+}
+impl Read<Tick> for LinearOpticalModel {
+    fn read(&mut self, _data: Arc<Data<Tick>>) {}
+}
+impl Read<M2modes> for LinearOpticalModel {
+    fn read(&mut self, data: Arc<Data<M2modes>>) {
+        self.m2modes = DVector::from_column_slice(&(&data).to_vec());
+    }
+}
+impl Write<SegmentPiston> for LinearOpticalModel {
+    fn write(&mut self) -> Option<Arc<Data<SegmentPiston>>> {
+        Some(Arc::new(Data::new(
+            (&self.m2modes_to_segment_piston * &self.m2modes)
+                .as_slice()
+                .to_vec(),
+        )))
+    }
+}
+impl Write<SegmentWfeRms> for LinearOpticalModel {
+    fn write(&mut self) -> Option<Arc<Data<SegmentWfeRms>>> {
+        Some(Arc::new(Data::new(self.segment_wfe_rms())))
+    }
+}
+impl Write<WfeRms> for LinearOpticalModel {
+    fn write(&mut self) -> Option<Arc<Data<WfeRms>>> {
+        Some(Arc::new(Data::new(vec![self.wfe_rms()])))
+    }
+}
+
+// The glao `main` aliases `OpticalModel`'s `SegmentPiston`/`SegmentWfeRms`/
+// `WfeRms` as `SegmentResidualPiston`/`SegmentResidualWfeRms`/`ResidualWfeRms`
+// for the `science` actor; implement those directly so `LinearOpticalModel`
+// can be dropped into the same slot.
+impl Write<crate::SegmentResidualPiston> for LinearOpticalModel {
+    fn write(&mut self) -> Option<Arc<Data<crate::SegmentResidualPiston>>> {
+        Some(Arc::new(Data::new(
+            (&self.m2modes_to_segment_piston * &self.m2modes)
+                .as_slice()
+                .to_vec(),
+        )))
+    }
+}
+impl Write<crate::SegmentResidualWfeRms> for LinearOpticalModel {
+    fn write(&mut self) -> Option<Arc<Data<crate::SegmentResidualWfeRms>>> {
+        Some(Arc::new(Data::new(self.segment_wfe_rms())))
+    }
+}
+impl Write<crate::ResidualWfeRms> for LinearOpticalModel {
+    fn write(&mut self) -> Option<Arc<Data<crate::ResidualWfeRms>>> {
+        Some(Arc::new(Data::new(vec![self.wfe_rms()])))
+    }
+}
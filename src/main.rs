@@ -1,6 +1,18 @@
 use std::{fs::File, path::Path, sync::Arc, time::Instant};
 
+mod calibration_vault;
+mod error_budget;
+mod linear_optical_model;
+mod tomographic_reconstructor;
+
 use arrow::Arrow;
+use calibration_vault::CalibrationVault;
+use error_budget::ErrorBudget;
+use linear_optical_model::LinearOpticalModel;
+use tomographic_reconstructor::{
+    geometric_interaction, science_projection, Cn2Layer, GuideStar, TomographicReconstructor,
+    TOMO_LENSLET_SIDE,
+};
 use crseo::{
     calibrations::{Mirror, Segment},
     Atmosphere, Calibration, Diffractive, FromBuilder, Geometric, Gmt, ShackHartmann,
@@ -21,22 +33,33 @@ use skyangle::SkyAngle;
 use vec_box::vec_box;
 
 pub struct Reconstructor {
-    mat: Vec<na::DMatrix<f64>>,
+    vault: CalibrationVault,
+    mat: Vec<DMatrix<f64>>,
     u: Vec<f64>,
     y: na::DVector<f64>,
     n_y: usize,
 }
 impl Reconstructor {
-    pub fn new(mat: Vec<na::DMatrix<f64>>) -> Self {
+    pub fn new(vault: CalibrationVault) -> Self {
+        // `vault.reconstructor()` clones every per-guide-star matrix; do it
+        // once here and reuse the cache on every `update`, instead of paying
+        // that clone on every AO tick.
+        let mat = vault.reconstructor();
         let n_y = mat[0].nrows();
         mat.iter().for_each(|mat| assert_eq!(n_y, mat.nrows()));
         Self {
+            vault,
             mat,
             u: vec![],
             y: na::DVector::zeros(n_y),
             n_y,
         }
     }
+    /// Length of the full, zero-reinserted per-segment mode vector produced
+    /// by [`Write::write`], summed over all 7 segments.
+    pub fn full_len(&self) -> usize {
+        7 * (self.n_y / 7 + self.vault.zeros().len())
+    }
 }
 impl Update for Reconstructor {
     fn update(&mut self) {
@@ -64,13 +87,19 @@ impl Read<SensorData> for Reconstructor {
 enum M2modesRec {}
 impl Write<M2modesRec> for Reconstructor {
     fn write(&mut self) -> Option<Arc<Data<M2modesRec>>> {
+        let n_mode = self.full_len() / 7;
         Some(Arc::new(Data::new(
             self.y
                 .as_slice()
                 .chunks(self.n_y / 7)
                 .flat_map(|y| {
-                    let mut a = vec![0f64];
-                    a.extend_from_slice(y);
+                    let mut a = vec![0f64; n_mode];
+                    let mut y = y.iter();
+                    for (k, a_k) in a.iter_mut().enumerate() {
+                        if !self.vault.zeros().contains(&k) {
+                            *a_k = *y.next().expect("mode vector shorter than expected");
+                        }
+                    }
                     a
                 })
                 .collect::<Vec<f64>>(),
@@ -78,6 +107,39 @@ impl Write<M2modesRec> for Reconstructor {
     }
 }
 
+/// Dispatches the `M2modesRec` estimation to either the ground-layer
+/// `Reconstructor` (implicit average of the guide-star directions) or the
+/// `TomographicReconstructor` (MMSE, full Cn² profile), so the GLAO actor
+/// graph is wired the same way regardless of which mode is selected.
+pub enum AoReconstructor {
+    GroundLayer(Reconstructor),
+    Tomographic(TomographicReconstructor),
+}
+impl Update for AoReconstructor {
+    fn update(&mut self) {
+        match self {
+            Self::GroundLayer(r) => r.update(),
+            Self::Tomographic(r) => r.update(),
+        }
+    }
+}
+impl Read<SensorData> for AoReconstructor {
+    fn read(&mut self, data: Arc<Data<SensorData>>) {
+        match self {
+            Self::GroundLayer(r) => r.read(data),
+            Self::Tomographic(r) => r.read(data),
+        }
+    }
+}
+impl Write<M2modesRec> for AoReconstructor {
+    fn write(&mut self) -> Option<Arc<Data<M2modesRec>>> {
+        match self {
+            Self::GroundLayer(r) => r.write(),
+            Self::Tomographic(r) => r.write(),
+        }
+    }
+}
+
 #[derive(UID)]
 #[alias(name = "Wavefront", client = "OpticalModel", traits = "Write,Size")]
 enum ResidualWavefront {}
@@ -107,6 +169,7 @@ enum NaturalSeeingPSSnFwhm {}
 #[alias(name = "PSSnFwhm", client = "OpticalModel", traits = "Write,Size")]
 enum GlaoPSSnFwhm {}
 
+#[derive(Clone, Copy, Debug)]
 #[allow(dead_code)]
 enum AtmosphereTurbulence {
     GroundLayer,
@@ -114,6 +177,36 @@ enum AtmosphereTurbulence {
     Free,
 }
 
+/// Discrete Cn² profile (layer altitude + fractional r₀) matching an
+/// [`AtmosphereTurbulence`] choice, for the tomographic reconstructor.
+fn cn2_profile(turbulence: AtmosphereTurbulence) -> Vec<Cn2Layer> {
+    match turbulence {
+        AtmosphereTurbulence::GroundLayer => vec![Cn2Layer {
+            altitude: 0.,
+            fractional_r0: 1.,
+        }],
+        AtmosphereTurbulence::Free => vec![Cn2Layer {
+            altitude: 7_000.,
+            fractional_r0: 1.,
+        }],
+        AtmosphereTurbulence::SevenLayers => vec![
+            (0., 0.30),
+            (500., 0.15),
+            (1_000., 0.12),
+            (2_000., 0.12),
+            (4_000., 0.10),
+            (8_000., 0.11),
+            (16_000., 0.10),
+        ]
+        .into_iter()
+        .map(|(altitude, fractional_r0)| Cn2Layer {
+            altitude,
+            fractional_r0,
+        })
+        .collect(),
+    }
+}
+
 /*
 V PSSN:
  5s: 1.0722716460275097
@@ -124,10 +217,52 @@ H PSSN
 30s: 1.02788736839214
 */
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    env_logger::init();
+/// Final-tick residual metrics of a single [`run_draw`] realization, for the
+/// Monte-Carlo ensemble driver in [`main`] to accumulate mean/std over.
+#[derive(Clone, Default)]
+struct DrawMetrics {
+    wfe_rms: f64,
+    segment_piston: Vec<f64>,
+    natural_seeing_pssn: f64,
+    glao_pssn: f64,
+}
+
+/// Terminator client that squirrels away the latest `ResidualWfeRms`,
+/// `SegmentResidualPiston`, `NaturalSeeingPSSnFwhm` and `GlaoPSSnFwhm` samples
+/// into a shared [`DrawMetrics`], so [`run_draw`] can hand its final-tick
+/// values back to [`main`] without exposing any actor-graph internals across
+/// the `adaptive_optics_system`/`detector_readouts` model boundary.
+struct EnsembleProbe {
+    metrics: Arc<std::sync::Mutex<DrawMetrics>>,
+}
+impl Update for EnsembleProbe {
+    fn update(&mut self) {}
+}
+impl Read<ResidualWfeRms> for EnsembleProbe {
+    fn read(&mut self, data: Arc<Data<ResidualWfeRms>>) {
+        self.metrics.lock().unwrap().wfe_rms = (&data).to_vec()[0];
+    }
+}
+impl Read<SegmentResidualPiston> for EnsembleProbe {
+    fn read(&mut self, data: Arc<Data<SegmentResidualPiston>>) {
+        self.metrics.lock().unwrap().segment_piston = (&data).to_vec();
+    }
+}
+impl Read<NaturalSeeingPSSnFwhm> for EnsembleProbe {
+    fn read(&mut self, data: Arc<Data<NaturalSeeingPSSnFwhm>>) {
+        self.metrics.lock().unwrap().natural_seeing_pssn = (&data).to_vec()[0];
+    }
+}
+impl Read<GlaoPSSnFwhm> for EnsembleProbe {
+    fn read(&mut self, data: Arc<Data<GlaoPSSnFwhm>>) {
+        self.metrics.lock().unwrap().glao_pssn = (&data).to_vec()[0];
+    }
+}
 
+/// Runs one full closed-loop realization for the given atmosphere `seed` and
+/// returns its final-tick metrics, for the Monte-Carlo ensemble driver in
+/// [`main`] to accumulate over.
+async fn run_draw(seed: u64) -> anyhow::Result<DrawMetrics> {
     let sim_duration = 5_usize;
     let atm_sampling_frequency = 1000_usize;
     const AO_RATE: usize = 10;
@@ -136,22 +271,40 @@ async fn main() -> anyhow::Result<()> {
     let n_sensor = 3;
     let guide_star_z_arcmin = 6f32;
 
+    // Reconstruction mode: implicit ground-layer average (GLAO) or MMSE
+    // tomographic estimate of the on-axis M2 modes from the full Cn² profile.
+    const TOMOGRAPHIC: bool = false;
+    // Exercise the ray-trace-free `LinearOpticalModel` residual-metric path,
+    // for fast gain/threshold tuning; it reuses the same M2-KL sensitivities
+    // as calibration and skips the diffractive `science` ray trace entirely,
+    // so it's evaluated standalone rather than in place of `science` here,
+    // which must stay ray-traced to advance the atmosphere for the final
+    // PSF/PSSn snapshots below.
+    const FAST_RESIDUALS: bool = false;
+    // Sweep `guide_star_z_arcmin` through the reactive `ErrorBudget` instead
+    // of relaunching the simulation once per point; see `error_budget` for
+    // which terms actually move under that sweep.
+    const ERROR_BUDGET_SWEEP: bool = false;
+
     // Science definition
     let src = crseo::Source::builder().band("V");
 
     // Atmosphere model
     let atm_duration = 1f32;
     let atm_n_duration = 31;
-    let atm = match AtmosphereTurbulence::Free {
+    let turbulence = AtmosphereTurbulence::Free;
+    let atm = match turbulence {
         AtmosphereTurbulence::SevenLayers => OpticalModelOptions::Atmosphere {
-            builder: Atmosphere::builder().ray_tracing(
-                25.5,
-                1020,
-                SkyAngle::Arcminute(20f32).to_radians(),
-                atm_duration,
-                Some("glao_atmosphere.bin".to_string()),
-                Some(atm_n_duration),
-            ),
+            builder: Atmosphere::builder()
+                .ray_tracing(
+                    25.5,
+                    1020,
+                    SkyAngle::Arcminute(20f32).to_radians(),
+                    atm_duration,
+                    Some(format!("glao_atmosphere-seed{seed}.bin")),
+                    Some(atm_n_duration),
+                )
+                .seed(seed as i32),
             time_step: 1e-3,
         },
         AtmosphereTurbulence::Free => OpticalModelOptions::Atmosphere {
@@ -161,10 +314,11 @@ async fn main() -> anyhow::Result<()> {
                     1020,
                     SkyAngle::Arcminute(20f32).to_radians(),
                     atm_duration,
-                    Some("glao_free-atmosphere.bin".to_string()),
+                    Some(format!("glao_free-atmosphere-seed{seed}.bin")),
                     Some(atm_n_duration),
                 )
-                .remove_turbulence_layer(0),
+                .remove_turbulence_layer(0)
+                .seed(seed as i32),
             time_step: 1e-3,
         },
         AtmosphereTurbulence::GroundLayer => OpticalModelOptions::Atmosphere {
@@ -175,9 +329,10 @@ async fn main() -> anyhow::Result<()> {
                     1020,
                     SkyAngle::Arcminute(20f32).to_radians(),
                     atm_duration,
-                    Some("ground_layer_atmosphere.bin".to_string()),
+                    Some(format!("ground_layer_atmosphere-seed{seed}.bin")),
                     Some(atm_n_duration),
-                ),
+                )
+                .seed(seed as i32),
             time_step: 1e-3,
         },
     };
@@ -209,14 +364,16 @@ async fn main() -> anyhow::Result<()> {
         .options(vec![
             imgr.clone(),
             OpticalModelOptions::Atmosphere {
-                builder: Atmosphere::builder().ray_tracing(
-                    25.5,
-                    1020,
-                    SkyAngle::Arcminute(20f32).to_radians(),
-                    atm_duration,
-                    Some("glao_atmosphere.bin".to_string()),
-                    Some(atm_n_duration),
-                ),
+                builder: Atmosphere::builder()
+                    .ray_tracing(
+                        25.5,
+                        1020,
+                        SkyAngle::Arcminute(20f32).to_radians(),
+                        atm_duration,
+                        Some(format!("glao_atmosphere-seed{seed}.bin")),
+                        Some(atm_n_duration),
+                    )
+                    .seed(seed as i32),
                 time_step: 1e-3,
             },
             pssn.clone(),
@@ -261,19 +418,27 @@ async fn main() -> anyhow::Result<()> {
         .options(vec![wfs, atm])
         .build()?;
 
-    // Poke matrix pseudo-inverse
+    // Poke matrix SVD, cached on disk and re-thresholded without recomputing it
     let path = format!(
-        "pinv_poke_{}mode_{}lensletX{}.bin",
+        "svd_poke_{}mode_{}lensletX{}.bin",
         m2_n_mode, n_side_lenslet, n_source
     );
     let calib_path = Path::new(&path);
-    let pinv_poke_mat: Vec<DMatrix<f64>> = if calib_path.is_file() {
-        println!("Loading pseudo-inverse from {:?}", calib_path);
-        let data: Vec<((usize, usize), Vec<f64>)> =
+    let mut vault = if calib_path.is_file() {
+        println!("Loading poke matrix SVD from {:?}", calib_path);
+        let data: Vec<((usize, usize), Vec<f64>, Vec<f64>, (usize, usize), Vec<f64>)> =
             bincode::deserialize_from(File::open(calib_path)?)?;
-        data.into_iter()
-            .map(|((n, m), x)| DMatrix::from_column_slice(n, m, x.as_slice()))
-            .collect()
+        let svd = data
+            .into_iter()
+            .map(|(u_shape, u, s, vt_shape, vt)| {
+                (
+                    DMatrix::from_column_slice(u_shape.0, u_shape.1, &u),
+                    na::DVector::from_column_slice(&s),
+                    DMatrix::from_column_slice(vt_shape.0, vt_shape.1, &vt),
+                )
+            })
+            .collect();
+        CalibrationVault::from_svd(svd)
     } else {
         let n_valid_lenslet = adaptive_optics
             .sensor
@@ -284,7 +449,7 @@ async fn main() -> anyhow::Result<()> {
         println!("# of valid lenslet: {:?}", n_valid_lenslet);
 
         // Computing & saving
-        println!("Computing AO poke matrix & pseudo-inverse");
+        println!("Computing AO poke matrix & SVD");
         let now = Instant::now();
         let mut calib = Calibration::new(
             &adaptive_optics.gmt,
@@ -309,7 +474,7 @@ async fn main() -> anyhow::Result<()> {
         let poke_mat = na::DMatrix::from_column_slice(poke.len() / n_mode, n_mode, &poke);
 
         let mut i = 0usize;
-        let mut pinv_poke_mat = vec![];
+        let mut sub_poke_mat = vec![];
         for &nv in &n_valid_lenslet {
             let rows: Vec<_> = poke_mat
                 .row_iter()
@@ -318,32 +483,129 @@ async fn main() -> anyhow::Result<()> {
                 .chain(poke_mat.row_iter().skip(i + n_nvl).take(nv))
                 .collect();
             i += nv;
-            let sub_poke_mat = na::DMatrix::from_rows(&rows);
-            let svd = sub_poke_mat.svd(false, false);
-            let svals = svd.singular_values.as_slice();
-            let condn = svals[0] / svals.last().unwrap();
-            println!("Condition #: {}", condn);
-
-            let sub_poke_mat = na::DMatrix::from_rows(&rows);
-            pinv_poke_mat.push(
-                sub_poke_mat
-                    .pseudo_inverse(0.)
-                    .expect("Failed to compute poke matrix pseudo-inverse"),
-            );
+            sub_poke_mat.push(na::DMatrix::from_rows(&rows));
+        }
+        println!("poke matrix SVD computed in {}ms", now.elapsed().as_millis());
+        let vault = CalibrationVault::new(sub_poke_mat);
+        for (k, (_, s, _)) in vault.svd().iter().enumerate() {
+            let svals = s.as_slice();
             println!(
-                "pseudo-inverse {:?} computed in {}ms",
-                pinv_poke_mat.last().unwrap().shape(),
-                now.elapsed().as_millis()
+                "WFS #{k} condition #: {}",
+                svals[0] / svals.last().unwrap()
             );
         }
-        println!("Saving pseudo-inverse to {:?}", calib_path);
-        let data: Vec<((usize, usize), Vec<f64>)> = pinv_poke_mat
+        println!("Saving poke matrix SVD to {:?}", calib_path);
+        let data: Vec<_> = vault
+            .svd()
             .iter()
-            .map(|x| (x.shape(), x.as_slice().to_vec()))
+            .map(|(u, s, v_t)| {
+                (
+                    u.shape(),
+                    u.as_slice().to_vec(),
+                    s.as_slice().to_vec(),
+                    v_t.shape(),
+                    v_t.as_slice().to_vec(),
+                )
+            })
             .collect();
         bincode::serialize_into(File::create(calib_path)?, &data)?;
-        pinv_poke_mat
+        vault
     };
+    // Drop the segment piston (mode index 0), left uncontrolled by the M2-KL
+    // reconstruction, and re-insert it as zero in `Reconstructor::write`.
+    vault.insert_zeros(vec![0]).n_threshold(0);
+
+    // V-band Fried parameter [m] and von Kármán outer scale [m] of the
+    // profile built by `cn2_profile`; both feed the per-layer phase
+    // covariance of the tomographic reconstructor and the error budget.
+    const R0: f64 = 0.15;
+    const OUTER_SCALE: f64 = 25.;
+
+    let ao_reconstructor = if TOMOGRAPHIC {
+        let cn2 = cn2_profile(turbulence);
+        let guide_stars: Vec<GuideStar> = (0..n_sensor)
+            .map(|k| GuideStar {
+                zenith: SkyAngle::Arcminute(guide_star_z_arcmin).to_radians() as f64,
+                azimuth: 2. * std::f64::consts::PI * k as f64 / n_sensor as f64,
+            })
+            .collect();
+        // Tomography runs on its own coarse geometric grid (`TOMO_LENSLET_SIDE`),
+        // decoupled from the M2-KL poke matrix: modal coefficients aren't
+        // spatial phase-grid points, so a genuine finite-difference
+        // interaction matrix is built directly on a square grid instead. No
+        // layer-conjugated WFS calibration is available yet, so every Cn²
+        // layer starts from the same on-axis interaction matrix; each guide
+        // star's line of sight through each layer is then genuinely
+        // footprint-shifted (altitude + azimuth, see `shift_interaction`).
+        let interaction = geometric_interaction(TOMO_LENSLET_SIDE);
+        let n_phi_per_layer = interaction.ncols();
+        let layer_interaction = vec![interaction; cn2.len()];
+        let n_mode_out = (m2_n_mode - 1) * 7;
+        let projection = science_projection(&cn2, n_phi_per_layer, n_mode_out, R0, OUTER_SCALE);
+        // Keyed on the same profile + asterism geometry that actually change
+        // the reconstructor, same spirit as `svd_poke_*` above.
+        let tomo_path_str = format!(
+            "tomo_reconstructor_{:?}_{}layerX{}lensletX{}sensorX{:.1}arcmin_r0-{:.3}_L0-{:.1}.bin",
+            turbulence,
+            cn2.len(),
+            TOMO_LENSLET_SIDE,
+            n_sensor,
+            guide_star_z_arcmin,
+            R0,
+            OUTER_SCALE
+        );
+        let tomo_path = Path::new(&tomo_path_str);
+        AoReconstructor::Tomographic(TomographicReconstructor::cached(
+            tomo_path,
+            &cn2,
+            &layer_interaction,
+            &guide_stars,
+            &projection,
+            R0,
+            OUTER_SCALE,
+            1e-14,
+        )?)
+    } else {
+        AoReconstructor::GroundLayer(Reconstructor::new(vault))
+    };
+
+    if ERROR_BUDGET_SWEEP {
+        let cn2 = cn2_profile(turbulence);
+        // Same coarse geometric grid as the `TOMOGRAPHIC` path above: no
+        // layer-conjugated WFS calibration is available yet, but each guide
+        // star's footprint shift is still genuine (altitude + azimuth), so
+        // the sweep below reflects real guide-star geometry.
+        let interaction = geometric_interaction(TOMO_LENSLET_SIDE);
+        let n_phi_per_layer = interaction.ncols();
+        let layer_interaction = vec![interaction; cn2.len()];
+        let n_mode_out = (m2_n_mode - 1) * 7;
+        let projection = science_projection(&cn2, n_phi_per_layer, n_mode_out, R0, OUTER_SCALE);
+        let mut budget = ErrorBudget::new(
+            layer_interaction,
+            projection,
+            n_mode,
+            n_side_lenslet,
+            guide_star_z_arcmin,
+            n_sensor,
+            0.5,
+            1e-14,
+            R0,
+            OUTER_SCALE,
+            cn2,
+        );
+        for z_arcmin in [1f32, 3., 6., 9., 12.] {
+            let wfe = budget.set_guide_star_z_arcmin(z_arcmin).budget();
+            println!(
+                "guide star @ {z_arcmin:.1}': tomographic = {:.3e}, fitting = {:.3e}, \
+                 aliasing = {:.3e}, noise = {:.3e}, rss = {:.3e}",
+                wfe.tomographic,
+                wfe.fitting,
+                wfe.aliasing,
+                wfe.noise,
+                wfe.rss()
+            );
+        }
+    }
 
     let adaptive_optics = adaptive_optics.into_arcx();
     let mut ao_actor: Actor<_, 1, AO_RATE> =
@@ -352,14 +614,14 @@ async fn main() -> anyhow::Result<()> {
     // Telemetry logs
     //  . WFE terms
     let logging = Arrow::builder(n_step)
-        .filename("glao-logs")
+        .filename(format!("glao-logs-seed{seed}"))
         .build()
         .into_arcx();
     let mut logs: Terminator<_> = Actor::new(logging.clone()).name("Logs");
     //  . Last wavefronts
     let wavefront_logging = Arrow::builder(1)
         .decimation(n_step)
-        .filename("glao-wavefront")
+        .filename(format!("glao-wavefront-seed{seed}"))
         .build()
         .into_arcx();
     let mut wavefront_logs: Terminator<_> =
@@ -399,6 +661,22 @@ async fn main() -> anyhow::Result<()> {
         .log(&mut logs)
         .await;
 
+    // Ensemble probe: captures the final-tick residual metrics for the
+    // Monte-Carlo driver in `main` to accumulate across draws.
+    let draw_metrics = Arc::new(std::sync::Mutex::new(DrawMetrics::default()));
+    let mut ensemble_probe: Terminator<_> = Actor::new(EnsembleProbe {
+        metrics: draw_metrics.clone(),
+    })
+    .name("Ensemble Probe");
+    science
+        .add_output()
+        .build::<ResidualWfeRms>()
+        .into_input(&mut ensemble_probe);
+    science
+        .add_output()
+        .build::<SegmentResidualPiston>()
+        .into_input(&mut ensemble_probe);
+
     on_axis
         .add_output()
         .build::<Wavefront>()
@@ -416,7 +694,7 @@ async fn main() -> anyhow::Result<()> {
 
     // WFS 2 M2 modes reconstructor
     let mut reconstructor: Actor<_, AO_RATE, AO_RATE> =
-        (Reconstructor::new(pinv_poke_mat), "M2 modes\nreconstructor").into();
+        (ao_reconstructor, "M2 modes\nreconstructor").into();
     ao_actor
         .add_output()
         .build::<SensorData>()
@@ -449,6 +727,7 @@ async fn main() -> anyhow::Result<()> {
         wavefront_logs,
         gmt,
         science,
+        ensemble_probe,
     ])
     .name("glao")
     .flowchart()
@@ -461,7 +740,10 @@ async fn main() -> anyhow::Result<()> {
         let mut on_axis: Actor<_> =
             Actor::new(optical_model.clone()).name("On-axis GMT\nw/ Atmosphere");
         let mut science: Actor<_> = Actor::new(science_path.clone()).name("Science Path");
-        let logging = Arrow::builder(1).filename("glao-frame").build().into_arcx();
+        let logging = Arrow::builder(1)
+            .filename(format!("glao-frame-seed{seed}"))
+            .build()
+            .into_arcx();
         let mut logs: Terminator<_> = Actor::new(logging.clone()).name("Logs");
 
         timer
@@ -496,7 +778,23 @@ async fn main() -> anyhow::Result<()> {
             .build::<GlaoPSSnFwhm>()
             .log(&mut logs)
             .await;
-        Model::new(vec_box!(timer, on_axis, science, logs))
+
+        let mut ensemble_probe: Terminator<_> = Actor::new(EnsembleProbe {
+            metrics: draw_metrics.clone(),
+        })
+        .name("Ensemble Probe");
+        on_axis
+            .add_output()
+            .bootstrap()
+            .build::<NaturalSeeingPSSnFwhm>()
+            .into_input(&mut ensemble_probe);
+        science
+            .add_output()
+            .bootstrap()
+            .build::<GlaoPSSnFwhm>()
+            .into_input(&mut ensemble_probe);
+
+        Model::new(vec_box!(timer, on_axis, science, logs, ensemble_probe))
             .name("glao-images")
             .check()?
             .flowchart()
@@ -505,5 +803,362 @@ async fn main() -> anyhow::Result<()> {
     adaptive_optics_system.wait().await?;
     detector_readouts.run().wait().await?;
 
+    if FAST_RESIDUALS {
+        // Sensitivities built once from the M2-KL modal convention shared
+        // with calibration (mode index 0 of each segment's block is segment
+        // piston, see `vault.insert_zeros` above), not a ray trace; see
+        // `LinearOpticalModel::kl_sensitivities`.
+        let mut lom = LinearOpticalModel::cached(Path::new("lom_sensitivities.bin"), || {
+            LinearOpticalModel::kl_sensitivities(7, m2_n_mode)
+        })?;
+        let demo_modes: Vec<f64> = (0..n_mode)
+            .map(|k| if k % m2_n_mode == 0 { 0. } else { 1e-7 })
+            .collect();
+        Read::<M2modes>::read(&mut lom, Arc::new(Data::new(demo_modes)));
+        lom.update();
+        let wfe_rms: Option<Arc<Data<WfeRms>>> = Write::<WfeRms>::write(&mut lom);
+        println!(
+            "Fast residual-metric path ready: WfeRms = {:?}",
+            wfe_rms.map(|data| (&data).to_vec())
+        );
+    }
+
+
+    Ok(draw_metrics.lock().unwrap().clone())
+}
+
+#[derive(UID)]
+enum EnsembleWfeRms {}
+#[derive(UID)]
+enum EnsembleSegmentPiston {}
+#[derive(UID)]
+enum EnsembleNaturalSeeingPssn {}
+#[derive(UID)]
+enum EnsembleGlaoPssn {}
+
+/// Replays a completed ensemble of [`DrawMetrics`], one draw per `Tick`, so
+/// the per-draw metrics can be logged as ordinary Arrow rows the same way the
+/// live `adaptive_optics_system`/`detector_readouts` metrics are.
+struct EnsembleReplay {
+    draws: Vec<DrawMetrics>,
+    index: usize,
+    started: bool,
+}
+impl EnsembleReplay {
+    fn new(draws: Vec<DrawMetrics>) -> Self {
+        Self {
+            draws,
+            index: 0,
+            started: false,
+        }
+    }
+}
+impl Update for EnsembleReplay {
+    fn update(&mut self) {}
+}
+impl Read<Tick> for EnsembleReplay {
+    fn read(&mut self, _data: Arc<Data<Tick>>) {
+        // Timer fires once per draw; advance past the draw already served.
+        if self.started {
+            self.index += 1;
+        }
+        self.started = true;
+    }
+}
+impl Write<EnsembleWfeRms> for EnsembleReplay {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleWfeRms>>> {
+        Some(Arc::new(Data::new(vec![self.draws.get(self.index)?.wfe_rms])))
+    }
+}
+impl Write<EnsembleSegmentPiston> for EnsembleReplay {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleSegmentPiston>>> {
+        Some(Arc::new(Data::new(
+            self.draws.get(self.index)?.segment_piston.clone(),
+        )))
+    }
+}
+impl Write<EnsembleNaturalSeeingPssn> for EnsembleReplay {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleNaturalSeeingPssn>>> {
+        Some(Arc::new(Data::new(vec![
+            self.draws.get(self.index)?.natural_seeing_pssn,
+        ])))
+    }
+}
+impl Write<EnsembleGlaoPssn> for EnsembleReplay {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleGlaoPssn>>> {
+        Some(Arc::new(Data::new(vec![self.draws.get(self.index)?.glao_pssn])))
+    }
+}
+
+#[derive(UID)]
+enum EnsembleWfeRmsMean {}
+#[derive(UID)]
+enum EnsembleWfeRmsStd {}
+#[derive(UID)]
+enum EnsembleNaturalSeeingPssnMean {}
+#[derive(UID)]
+enum EnsembleNaturalSeeingPssnStd {}
+#[derive(UID)]
+enum EnsembleGlaoPssnMean {}
+#[derive(UID)]
+enum EnsembleGlaoPssnStd {}
+#[derive(UID)]
+enum EnsembleSegmentPistonMean {}
+#[derive(UID)]
+enum EnsembleSegmentPistonStd {}
+
+/// Mean and standard deviation, across the ensemble's draws, of every
+/// [`DrawMetrics`] field, so performance can be quoted with error bars
+/// instead of a single lucky draw. Computed directly from the accumulated
+/// draws, without running any part of the actor model.
+struct EnsembleSummary {
+    wfe_rms: (f64, f64),
+    natural_seeing_pssn: (f64, f64),
+    glao_pssn: (f64, f64),
+    segment_piston: (Vec<f64>, Vec<f64>),
+}
+/// Population mean and standard deviation of `xs`.
+fn mean_std(xs: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let n = xs.clone().count() as f64;
+    let mean = xs.clone().sum::<f64>() / n;
+    let var = xs.map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+impl EnsembleSummary {
+    fn new(draws: &[DrawMetrics]) -> Self {
+        let n_segment = draws.first().map_or(0, |d| d.segment_piston.len());
+        let (piston_mean, piston_std) = (0..n_segment)
+            .map(|k| mean_std(draws.iter().map(|d| d.segment_piston[k])))
+            .unzip();
+        Self {
+            wfe_rms: mean_std(draws.iter().map(|d| d.wfe_rms)),
+            natural_seeing_pssn: mean_std(draws.iter().map(|d| d.natural_seeing_pssn)),
+            glao_pssn: mean_std(draws.iter().map(|d| d.glao_pssn)),
+            segment_piston: (piston_mean, piston_std),
+        }
+    }
+}
+impl Update for EnsembleSummary {
+    fn update(&mut self) {}
+}
+impl Read<Tick> for EnsembleSummary {
+    fn read(&mut self, _data: Arc<Data<Tick>>) {}
+}
+impl Write<EnsembleWfeRmsMean> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleWfeRmsMean>>> {
+        Some(Arc::new(Data::new(vec![self.wfe_rms.0])))
+    }
+}
+impl Write<EnsembleWfeRmsStd> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleWfeRmsStd>>> {
+        Some(Arc::new(Data::new(vec![self.wfe_rms.1])))
+    }
+}
+impl Write<EnsembleNaturalSeeingPssnMean> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleNaturalSeeingPssnMean>>> {
+        Some(Arc::new(Data::new(vec![self.natural_seeing_pssn.0])))
+    }
+}
+impl Write<EnsembleNaturalSeeingPssnStd> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleNaturalSeeingPssnStd>>> {
+        Some(Arc::new(Data::new(vec![self.natural_seeing_pssn.1])))
+    }
+}
+impl Write<EnsembleGlaoPssnMean> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleGlaoPssnMean>>> {
+        Some(Arc::new(Data::new(vec![self.glao_pssn.0])))
+    }
+}
+impl Write<EnsembleGlaoPssnStd> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleGlaoPssnStd>>> {
+        Some(Arc::new(Data::new(vec![self.glao_pssn.1])))
+    }
+}
+impl Write<EnsembleSegmentPistonMean> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleSegmentPistonMean>>> {
+        Some(Arc::new(Data::new(self.segment_piston.0.clone())))
+    }
+}
+impl Write<EnsembleSegmentPistonStd> for EnsembleSummary {
+    fn write(&mut self) -> Option<Arc<Data<EnsembleSegmentPistonStd>>> {
+        Some(Arc::new(Data::new(self.segment_piston.1.clone())))
+    }
+}
+
+/// Monte-Carlo ensemble driver: runs [`run_draw`] once per atmosphere seed,
+/// then logs both the per-draw residual metrics and their ensemble mean/std
+/// so GLAO performance can be quoted with error bars instead of a single
+/// lucky draw.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    // Ensemble size and seeds: bump `N_DRAW` (and/or the seed list) to widen
+    // the Monte-Carlo sample.
+    const N_DRAW: usize = 4;
+    let seeds: Vec<u64> = (0..N_DRAW as u64).collect();
+
+    let mut draws = Vec::with_capacity(seeds.len());
+    for &seed in &seeds {
+        println!("### Draw (seed {seed}) ###");
+        draws.push(run_draw(seed).await?);
+    }
+
+    // Per-draw Arrow log: one row per realization.
+    {
+        let mut timer: Initiator<_> = Timer::new(draws.len() - 1).into();
+        let mut replay: Actor<_> = Actor::new(EnsembleReplay::new(draws.clone())).name("Ensemble Replay");
+        let logging = Arrow::builder(draws.len())
+            .filename("glao-ensemble")
+            .build()
+            .into_arcx();
+        let mut logs: Terminator<_> = Actor::new(logging.clone()).name("Ensemble Logs");
+        timer
+            .add_output()
+            .build::<Tick>()
+            .into_input(&mut replay)
+            .confirm()?;
+        replay
+            .add_output()
+            .build::<EnsembleWfeRms>()
+            .log(&mut logs)
+            .await;
+        replay
+            .add_output()
+            .build::<EnsembleSegmentPiston>()
+            .log(&mut logs)
+            .await;
+        replay
+            .add_output()
+            .build::<EnsembleNaturalSeeingPssn>()
+            .log(&mut logs)
+            .await;
+        replay
+            .add_output()
+            .build::<EnsembleGlaoPssn>()
+            .log(&mut logs)
+            .await;
+        Model::new(vec_box![timer, replay, logs])
+            .name("glao-ensemble")
+            .check()?
+            .flowchart()
+            .run()
+            .wait()
+            .await?;
+    }
+
+    // Ensemble summary: one-row mean/std Arrow log, plus a console readout.
+    let summary = EnsembleSummary::new(&draws);
+    println!(
+        "Ensemble of {} draws: WfeRms = {:.3e} +/- {:.3e}, GlaoPSSn = {:.3} +/- {:.3}",
+        draws.len(),
+        summary.wfe_rms.0,
+        summary.wfe_rms.1,
+        summary.glao_pssn.0,
+        summary.glao_pssn.1
+    );
+    {
+        let mut timer: Initiator<_> = Timer::new(0).into();
+        let mut summary_actor: Actor<_> = Actor::new(summary).name("Ensemble Summary");
+        let logging = Arrow::builder(1)
+            .filename("glao-ensemble-summary")
+            .build()
+            .into_arcx();
+        let mut logs: Terminator<_> = Actor::new(logging.clone()).name("Ensemble Summary Logs");
+        timer
+            .add_output()
+            .build::<Tick>()
+            .into_input(&mut summary_actor)
+            .confirm()?;
+        summary_actor
+            .add_output()
+            .build::<EnsembleWfeRmsMean>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleWfeRmsStd>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleNaturalSeeingPssnMean>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleNaturalSeeingPssnStd>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleGlaoPssnMean>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleGlaoPssnStd>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleSegmentPistonMean>()
+            .log(&mut logs)
+            .await;
+        summary_actor
+            .add_output()
+            .build::<EnsembleSegmentPistonStd>()
+            .log(&mut logs)
+            .await;
+        Model::new(vec_box![timer, summary_actor, logs])
+            .name("glao-ensemble-summary")
+            .check()?
+            .flowchart()
+            .run()
+            .wait()
+            .await?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mean_std_of_constant_sequence_is_zero_std() {
+        let (mean, std) = mean_std([3., 3., 3., 3.].into_iter());
+        assert_eq!(mean, 3.);
+        assert_eq!(std, 0.);
+    }
+
+    #[test]
+    fn mean_std_matches_hand_computed_population_variance() {
+        // Population (not sample) variance: mean 3, deviations [-2,-1,0,1,2].
+        let (mean, std) = mean_std([1., 2., 3., 4., 5.].into_iter());
+        assert_eq!(mean, 3.);
+        assert!((std - 2f64.sqrt()).abs() < 1e-12);
+    }
+
+    fn draw(wfe_rms: f64, piston: Vec<f64>) -> DrawMetrics {
+        DrawMetrics {
+            wfe_rms,
+            segment_piston: piston,
+            natural_seeing_pssn: 0.,
+            glao_pssn: 0.,
+        }
+    }
+
+    #[test]
+    fn ensemble_summary_reduces_draws_to_mean_and_std_per_field() {
+        let draws = vec![
+            draw(1., vec![0., 1.]),
+            draw(3., vec![2., 3.]),
+        ];
+        let summary = EnsembleSummary::new(&draws);
+        assert_eq!(summary.wfe_rms, (2., 1.));
+        assert_eq!(summary.segment_piston.0, vec![1., 2.]);
+        assert_eq!(summary.segment_piston.1, vec![1., 1.]);
+    }
+}
@@ -0,0 +1,499 @@
+use std::{f64::consts::PI, fs::File, path::Path, sync::Arc};
+
+use na::{DMatrix, DVector};
+use nalgebra as na;
+
+use dos_actors::{
+    io::{Data, Read, Write},
+    Update,
+};
+use crseo_client::SensorData;
+
+use crate::M2modesRec;
+
+/// A single turbulence layer of a discrete Cn² profile: altitude [m] and
+/// fractional contribution to the total r₀, as handed out by the
+/// `Atmosphere` builder.
+#[derive(Clone, Copy, Debug)]
+pub struct Cn2Layer {
+    pub altitude: f64,
+    pub fractional_r0: f64,
+}
+
+/// Guide-star angular position on the sky, in radians from zenith.
+#[derive(Clone, Copy, Debug)]
+pub struct GuideStar {
+    pub zenith: f64,
+    pub azimuth: f64,
+}
+impl GuideStar {
+    /// Footprint offset, on a layer at `altitude`, of the line of sight
+    /// through this guide star.
+    fn footprint(&self, altitude: f64) -> (f64, f64) {
+        let r = altitude * self.zenith;
+        (r * self.azimuth.cos(), r * self.azimuth.sin())
+    }
+}
+
+/// Minimum-mean-square-error (MMSE) tomographic reconstructor.
+///
+/// Estimates the on-axis (science-direction) M2 modal coefficients from the
+/// concatenated slopes of the `n_sensor` guide stars, given a discrete Cn²
+/// profile:
+/// `R = Pa . Cphi . Mᵀ . (M . Cphi . Mᵀ + Cn)⁻¹`
+/// where `M` stacks, per guide star, the WFS interaction matrix of every
+/// turbulence layer shifted by that guide star's footprint, `Cphi` is the
+/// block-diagonal layer phase covariance (von Kármán, scaled by each layer's
+/// fractional r₀), `Cn` is the measurement-noise covariance and `Pa`
+/// projects the reconstructed layer phases onto the science direction and
+/// onto the M2 Karhunen-Loève modal basis. `R` is built once and cached to
+/// disk, keyed by the profile and asterism geometry, exactly like the GLAO
+/// poke-matrix reconstructor.
+pub struct TomographicReconstructor {
+    r: DMatrix<f64>,
+    u: Vec<f64>,
+    y: DVector<f64>,
+}
+impl TomographicReconstructor {
+    /// `layer_interaction`: per-layer WFS interaction matrix for a single
+    /// guide star on-axis (rows: 2 * n_valid_lenslet, cols: # of layer phase
+    /// points, sampled on a square grid over the aperture, e.g.
+    /// [`geometric_interaction`]); it is re-used, footprint-shifted, for
+    /// every guide star. `guide_stars`: the `n_sensor` off-axis directions
+    /// whose slopes are concatenated into the measurement vector.
+    /// `projection`: projects the stacked layer phases onto the science
+    /// direction and the M2 KL modal basis (rows: # of KL modes kept, cols:
+    /// sum of layer phase points), e.g. [`science_projection`]. `r0`/
+    /// `outer_scale`: total Fried parameter [m] and von Kármán outer scale
+    /// [m] of the profile, split across layers by each
+    /// [`Cn2Layer::fractional_r0`]. `noise_variance`: WFS measurement-noise
+    /// variance, assumed uniform across slopes.
+    pub fn new(
+        layers: &[Cn2Layer],
+        layer_interaction: &[DMatrix<f64>],
+        guide_stars: &[GuideStar],
+        projection: &DMatrix<f64>,
+        r0: f64,
+        outer_scale: f64,
+        noise_variance: f64,
+    ) -> Self {
+        let r = Self::build(
+            layers,
+            layer_interaction,
+            guide_stars,
+            projection,
+            r0,
+            outer_scale,
+            noise_variance,
+        );
+        let n_y = r.nrows();
+        Self {
+            r,
+            u: vec![],
+            y: DVector::zeros(n_y),
+        }
+    }
+    /// Loads a reconstructor cached by a previous call, or builds and caches
+    /// one if `path` doesn't exist yet.
+    pub fn cached(
+        path: &Path,
+        layers: &[Cn2Layer],
+        layer_interaction: &[DMatrix<f64>],
+        guide_stars: &[GuideStar],
+        projection: &DMatrix<f64>,
+        r0: f64,
+        outer_scale: f64,
+        noise_variance: f64,
+    ) -> anyhow::Result<Self> {
+        let r = if path.is_file() {
+            println!("Loading tomographic reconstructor from {:?}", path);
+            let (shape, data): ((usize, usize), Vec<f64>) =
+                bincode::deserialize_from(File::open(path)?)?;
+            DMatrix::from_column_slice(shape.0, shape.1, &data)
+        } else {
+            println!("Computing tomographic reconstructor");
+            let r = Self::build(
+                layers,
+                layer_interaction,
+                guide_stars,
+                projection,
+                r0,
+                outer_scale,
+                noise_variance,
+            );
+            println!("Saving tomographic reconstructor to {:?}", path);
+            bincode::serialize_into(File::create(path)?, &(r.shape(), r.as_slice().to_vec()))?;
+            r
+        };
+        let n_y = r.nrows();
+        Ok(Self {
+            r,
+            u: vec![],
+            y: DVector::zeros(n_y),
+        })
+    }
+    fn build(
+        layers: &[Cn2Layer],
+        layer_interaction: &[DMatrix<f64>],
+        guide_stars: &[GuideStar],
+        projection: &DMatrix<f64>,
+        r0: f64,
+        outer_scale: f64,
+        noise_variance: f64,
+    ) -> DMatrix<f64> {
+        let (m, c_phi, inv) = signal_and_covariance(
+            layers,
+            layer_interaction,
+            guide_stars,
+            r0,
+            outer_scale,
+            noise_variance,
+        );
+        projection * &c_phi * m.transpose() * inv
+    }
+}
+
+/// Builds the stacked guide-star interaction matrix `M`, the layer phase
+/// covariance `Cphi` and `(M . Cphi . Mᵀ + Cn)⁻¹`, shared by
+/// [`TomographicReconstructor::build`] and [`residual_covariance`] so the
+/// error budget doesn't have to re-derive the tomographic fusion from
+/// scratch.
+fn signal_and_covariance(
+    layers: &[Cn2Layer],
+    layer_interaction: &[DMatrix<f64>],
+    guide_stars: &[GuideStar],
+    r0: f64,
+    outer_scale: f64,
+    noise_variance: f64,
+) -> (DMatrix<f64>, DMatrix<f64>, DMatrix<f64>) {
+    assert_eq!(layers.len(), layer_interaction.len());
+    // M: one row-block per guide star, stacking the (footprint-shifted)
+    // per-layer interaction matrices side by side.
+    let n_slope = layer_interaction[0].nrows();
+    let n_phi: usize = layer_interaction.iter().map(|m| m.ncols()).sum();
+    let mut m = DMatrix::zeros(n_slope * guide_stars.len(), n_phi);
+    for (k, gs) in guide_stars.iter().enumerate() {
+        let mut j = 0;
+        for (layer, interaction) in layers.iter().zip(layer_interaction) {
+            let (dx, dy) = gs.footprint(layer.altitude);
+            let shifted = shift_interaction(interaction, dx, dy);
+            m.view_mut((k * n_slope, j), shifted.shape())
+                .copy_from(&shifted);
+            j += interaction.ncols();
+        }
+    }
+    // Cphi: block-diagonal von Kármán layer covariance, scaled by the
+    // layer's fractional r0.
+    let c_phi = {
+        let mut c_phi = DMatrix::zeros(n_phi, n_phi);
+        let mut j = 0;
+        for (layer, interaction) in layers.iter().zip(layer_interaction) {
+            let n = interaction.ncols();
+            let r0_layer = r0 * layer.fractional_r0.powf(-3. / 5.);
+            let block = von_karman_covariance(n, r0_layer, outer_scale);
+            c_phi.view_mut((j, j), (n, n)).copy_from(&block);
+            j += n;
+        }
+        c_phi
+    };
+    let c_n = DMatrix::from_diagonal_element(m.nrows(), m.nrows(), noise_variance);
+    let mcm_t = &m * &c_phi * m.transpose() + c_n;
+    let inv = mcm_t
+        .try_inverse()
+        .expect("failed to invert the tomographic signal+noise covariance");
+    (m, c_phi, inv)
+}
+
+/// Residual layer-phase covariance `Cphi - Cphi . Mᵀ . (M . Cphi . Mᵀ + Cn)⁻¹ . M . Cphi`
+/// left uncorrected by the MMSE tomographic fusion, for the
+/// [`crate::error_budget::ErrorBudget`] to project onto the science direction
+/// without running the full actor model.
+pub(crate) fn residual_covariance(
+    layers: &[Cn2Layer],
+    layer_interaction: &[DMatrix<f64>],
+    guide_stars: &[GuideStar],
+    r0: f64,
+    outer_scale: f64,
+    noise_variance: f64,
+) -> DMatrix<f64> {
+    let (m, c_phi, inv) = signal_and_covariance(
+        layers,
+        layer_interaction,
+        guide_stars,
+        r0,
+        outer_scale,
+        noise_variance,
+    );
+    &c_phi - &c_phi * m.transpose() * &inv * &m * &c_phi
+}
+impl Update for TomographicReconstructor {
+    fn update(&mut self) {
+        self.y = &self.r * DVector::from_column_slice(&self.u);
+    }
+}
+impl Read<SensorData> for TomographicReconstructor {
+    fn read(&mut self, data: Arc<Data<SensorData>>) {
+        self.u = (&data).to_vec();
+    }
+}
+impl Write<M2modesRec> for TomographicReconstructor {
+    fn write(&mut self) -> Option<Arc<Data<M2modesRec>>> {
+        Some(Arc::new(Data::new(self.y.as_slice().to_vec())))
+    }
+}
+
+/// GMT aperture diameter [m], the physical extent each layer's square
+/// `n`-point sampling grid is assumed to span; shared by [`shift_interaction`]
+/// and [`von_karman_covariance`] so a guide-star footprint shift and a phase
+/// separation refer to the same grid.
+pub(crate) const APERTURE_DIAMETER: f64 = 25.5;
+
+/// Lenslet count (per side) of the coarse geometric grid the tomographic
+/// reconstructor runs its per-layer phase covariance/interaction on. This is
+/// deliberately decoupled from the real SH-WFS's `n_side_lenslet`: the
+/// tomographic fusion only needs a grid coherent enough for a genuine 2-D
+/// footprint shift and a well-posed covariance eigendecomposition, not the
+/// full WFS resolution, and keeping it small keeps [`science_projection`]'s
+/// `symmetric_eigen` tractable.
+pub(crate) const TOMO_LENSLET_SIDE: usize = 10;
+
+/// Geometric (Hudgin/Fried-style) Shack-Hartmann interaction matrix: maps the
+/// `(n_lenslet_side + 1)²` phase points of a square grid spanning
+/// [`APERTURE_DIAMETER`] to `2 * n_lenslet_side²` x/y local-slope
+/// measurements, via the finite-difference average of the two phase edges
+/// each lenslet straddles. This supplies a genuine per-layer phase-point
+/// interaction matrix, in place of reusing the M2-KL poke matrix whose
+/// columns are modal coefficients, not spatial grid points.
+pub(crate) fn geometric_interaction(n_lenslet_side: usize) -> DMatrix<f64> {
+    let grid_side = n_lenslet_side + 1;
+    let n_phi = grid_side * grid_side;
+    let n_lenslet = n_lenslet_side * n_lenslet_side;
+    let pitch = APERTURE_DIAMETER / n_lenslet_side as f64;
+    let gain = 1. / (2. * pitch);
+    let mut interaction = DMatrix::zeros(2 * n_lenslet, n_phi);
+    for row in 0..n_lenslet_side {
+        for col in 0..n_lenslet_side {
+            let lenslet = row * n_lenslet_side + col;
+            let bl = row * grid_side + col;
+            let br = bl + 1;
+            let tl = bl + grid_side;
+            let tr = tl + 1;
+            interaction[(lenslet, br)] += gain;
+            interaction[(lenslet, bl)] -= gain;
+            interaction[(lenslet, tr)] += gain;
+            interaction[(lenslet, tl)] -= gain;
+            interaction[(n_lenslet + lenslet, tl)] += gain;
+            interaction[(n_lenslet + lenslet, bl)] -= gain;
+            interaction[(n_lenslet + lenslet, tr)] += gain;
+            interaction[(n_lenslet + lenslet, br)] -= gain;
+        }
+    }
+    interaction
+}
+
+/// Builds the science-direction/KL projection `Pa`: the on-axis line of
+/// sight sees the unshifted sum of every layer, so the stacked layer phases
+/// are first merged by plain addition, then projected onto a Karhunen-Loève
+/// modal basis. KL modes are, by definition, the eigenbasis of the phase
+/// covariance they are derived from, so that basis is built directly as the
+/// top `n_mode_out` eigenvectors (by decreasing eigenvalue, i.e. decreasing
+/// contribution to the total phase variance) of the combined (summed over
+/// layers) von Kármán covariance sampled on the same `n_phi_per_layer`-point
+/// grid [`geometric_interaction`] uses. The result is sized
+/// `min(n_mode_out, n_phi_per_layer) x (n_phi_per_layer * layers.len())`:
+/// no more modes than grid points are returned. Unlike an identity
+/// matrix it is well-defined (and non-degenerate) for any number of layers.
+pub(crate) fn science_projection(
+    layers: &[Cn2Layer],
+    n_phi_per_layer: usize,
+    n_mode_out: usize,
+    r0: f64,
+    outer_scale: f64,
+) -> DMatrix<f64> {
+    let total_cov = layers.iter().fold(
+        DMatrix::zeros(n_phi_per_layer, n_phi_per_layer),
+        |acc, layer| {
+            let r0_layer = r0 * layer.fractional_r0.powf(-3. / 5.);
+            acc + von_karman_covariance(n_phi_per_layer, r0_layer, outer_scale)
+        },
+    );
+    let eigen = total_cov.symmetric_eigen();
+    let mut order: Vec<usize> = (0..eigen.eigenvalues.len()).collect();
+    order.sort_by(|&i, &j| eigen.eigenvalues[j].partial_cmp(&eigen.eigenvalues[i]).unwrap());
+    let n_mode_out = n_mode_out.min(order.len());
+    let kl_transform = DMatrix::from_fn(n_mode_out, n_phi_per_layer, |i, j| {
+        eigen.eigenvectors[(j, order[i])]
+    });
+    let mut science_merge = DMatrix::zeros(n_phi_per_layer, n_phi_per_layer * layers.len());
+    for k in 0..layers.len() {
+        science_merge
+            .view_mut((0, k * n_phi_per_layer), (n_phi_per_layer, n_phi_per_layer))
+            .copy_from(&DMatrix::identity(n_phi_per_layer, n_phi_per_layer));
+    }
+    kl_transform * science_merge
+}
+
+/// Side length of the square grid `n` phase points are laid out on.
+fn grid_side(n: usize) -> usize {
+    (n as f64).sqrt().round() as usize
+}
+
+/// `(x, y)` position [m] of grid point `k`, row-major over a `side x side`
+/// grid spanning [`APERTURE_DIAMETER`].
+fn grid_coord(k: usize, side: usize) -> (f64, f64) {
+    let pitch = APERTURE_DIAMETER / side as f64;
+    ((k % side) as f64 * pitch, (k / side) as f64 * pitch)
+}
+
+/// Shifts a layer interaction matrix by a guide-star footprint offset
+/// `(dx, dy)` [m]: a genuine 2-D periodic translation of the layer's square
+/// sampling grid, rounded to the nearest grid point along each axis, so
+/// guide stars at different azimuths (not just different zenith distances)
+/// produce distinct shifted matrices.
+fn shift_interaction(interaction: &DMatrix<f64>, dx: f64, dy: f64) -> DMatrix<f64> {
+    let n = interaction.ncols();
+    let side = grid_side(n);
+    let pitch = APERTURE_DIAMETER / side as f64;
+    let col_shift = (dx / pitch).round() as i64;
+    let row_shift = (dy / pitch).round() as i64;
+    let side_i = side as i64;
+    let mut shifted = interaction.clone();
+    for j in 0..n {
+        let row = (j / side) as i64;
+        let col = (j % side) as i64;
+        let src_row = (row + row_shift).rem_euclid(side_i) as usize;
+        let src_col = (col + col_shift).rem_euclid(side_i) as usize;
+        shifted
+            .column_mut(j)
+            .copy_from(&interaction.column(src_row * side + src_col));
+    }
+    shifted
+}
+
+/// Lanczos approximation of the Gamma function (g = 7, n = 9 coefficients),
+/// accurate to ~1e-10 over the positive reals used here.
+fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_312e-7,
+    ];
+    if x < 0.5 {
+        PI / ((PI * x).sin() * gamma(1.0 - x))
+    } else {
+        let x = x - 1.0;
+        let t = x + G + 0.5;
+        let a = COEFFS[0]
+            + COEFFS[1..]
+                .iter()
+                .enumerate()
+                .map(|(i, c)| c / (x + i as f64 + 1.0))
+                .sum::<f64>();
+        (2.0 * PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * a
+    }
+}
+
+/// Modified Bessel function of the first kind, real order, from its
+/// defining power series; converges quickly for the moderate arguments
+/// (`2π·separation/outer_scale`, order ~5 at most) seen by
+/// [`von_karman_covariance`].
+fn bessel_i(nu: f64, x: f64) -> f64 {
+    let half_x_sq = (x / 2.0).powi(2);
+    let mut term = (x / 2.0).powf(nu) / gamma(nu + 1.0);
+    let mut sum = term;
+    let mut k = 1.0;
+    while term.abs() > sum.abs() * 1e-15 && k < 200.0 {
+        term *= half_x_sq / (k * (k + nu));
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+/// Modified Bessel function of the second kind, non-integer order, from the
+/// first-kind functions of order `±nu` (DLMF 10.27.4).
+fn bessel_k(nu: f64, x: f64) -> f64 {
+    PI / 2.0 * (bessel_i(-nu, x) - bessel_i(nu, x)) / (nu * PI).sin()
+}
+
+/// Von Kármán phase covariance [rad²] at separation `r` [m], for a layer of
+/// Fried parameter `r0` [m] and outer scale `outer_scale` [m] (Conan 2008,
+/// via the order-5/6 modified Bessel function of the second kind).
+fn von_karman_value(r: f64, r0: f64, outer_scale: f64) -> f64 {
+    let a = gamma(11.0 / 6.0) / (2f64.powf(5.0 / 6.0) * PI.powf(8.0 / 3.0))
+        * (24.0 / 5.0 * gamma(6.0 / 5.0)).powf(5.0 / 6.0);
+    let prefactor = a * r0.powf(-5.0 / 3.0) * outer_scale.powf(5.0 / 3.0);
+    let x = 2.0 * PI * r / outer_scale;
+    if x < 1e-6 {
+        // lim_{x->0} x^{5/6} K_{5/6}(x) = 2^{5/6 - 1} . Gamma(5/6)
+        prefactor * 2f64.powf(5.0 / 6.0 - 1.0) * gamma(5.0 / 6.0)
+    } else {
+        prefactor * x.powf(5.0 / 6.0) * bessel_k(5.0 / 6.0, x)
+    }
+}
+
+/// Von Kármán phase covariance of a layer sampled on an `n`-point square
+/// grid spanning [`APERTURE_DIAMETER`], for a layer of Fried parameter `r0`
+/// [m] and outer scale `outer_scale` [m].
+fn von_karman_covariance(n: usize, r0: f64, outer_scale: f64) -> DMatrix<f64> {
+    let side = grid_side(n);
+    let coord: Vec<(f64, f64)> = (0..n).map(|k| grid_coord(k, side)).collect();
+    DMatrix::from_fn(n, n, |i, j| {
+        let (xi, yi) = coord[i];
+        let (xj, yj) = coord[j];
+        // Zero separation is finite for a finite outer scale: the diagonal
+        // goes through the small-argument limit of `von_karman_value`.
+        let r = (xi - xj).hypot(yi - yj);
+        von_karman_value(r, r0, outer_scale)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn von_karman_covariance_is_symmetric_with_zero_separation_diagonal_variance() {
+        let cov = von_karman_covariance(9, 0.15, 25.);
+        assert!((&cov - cov.transpose()).norm() < 1e-9);
+        let diag_var = von_karman_value(0., 0.15, 25.);
+        for k in 0..9 {
+            assert!((cov[(k, k)] - diag_var).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn von_karman_covariance_decreases_with_separation() {
+        let cov = von_karman_covariance(9, 0.15, 25.);
+        // Point 0 and its immediate row/column neighbours are closer than
+        // the diagonally opposite corner of the 3x3 grid.
+        assert!(cov[(0, 0)] > cov[(0, 1)]);
+        assert!(cov[(0, 1)] > cov[(0, 8)]);
+    }
+
+    #[test]
+    fn shift_interaction_is_identity_for_zero_shift() {
+        let m = DMatrix::from_fn(9, 9, |i, j| (i * 9 + j) as f64);
+        let shifted = shift_interaction(&m, 0., 0.);
+        assert_eq!(m, shifted);
+    }
+
+    #[test]
+    fn shift_interaction_distinguishes_azimuth() {
+        // A 3x3 grid spanning APERTURE_DIAMETER: one pitch along x only vs.
+        // one pitch along y only must shift the columns differently,
+        // otherwise azimuth would be collapsing back to a scalar radius.
+        let pitch = APERTURE_DIAMETER / 3.;
+        let m = DMatrix::from_fn(9, 9, |i, j| (i * 9 + j) as f64);
+        let shifted_x = shift_interaction(&m, pitch, 0.);
+        let shifted_y = shift_interaction(&m, 0., pitch);
+        assert_ne!(shifted_x, shifted_y);
+    }
+}